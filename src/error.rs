@@ -0,0 +1,79 @@
+use std::ops::Range;
+
+use thiserror::Error;
+
+#[cfg(feature = "diagnostics")]
+use miette::Diagnostic;
+
+/// Errors produced while parsing a [`Pattern`](crate::pattern::Pattern).
+///
+/// Every variant carries the byte-offset [`Range<usize>`] into the original
+/// pattern string that triggered the failure, so callers can point at the
+/// exact location instead of parsing a flat message. Offsets always fall on
+/// UTF-8 character boundaries, even for multi-byte input, since the parser
+/// tracks positions via `char_indices`.
+///
+/// With the `diagnostics` feature enabled, `PatternError` implements
+/// [`miette::Diagnostic`] and can be reported with a labeled span pointing
+/// directly at the offending fragment of the source pattern.
+#[derive(Debug, Clone, PartialEq, Error)]
+#[cfg_attr(feature = "diagnostics", derive(Diagnostic))]
+pub enum PatternError {
+    /// A `{`, `}`, `[` or `]` showed up somewhere it cannot be nested.
+    #[error("unexpected character '{found}' at {span:?}")]
+    UnexpectedChar {
+        found: char,
+        #[cfg_attr(feature = "diagnostics", source_code)]
+        source_code: String,
+        #[cfg_attr(feature = "diagnostics", label("unexpected character"))]
+        span: Range<usize>,
+    },
+
+    /// A `{` was never closed by a matching `}`.
+    #[error("unterminated set starting at {span:?}")]
+    UnterminatedSet {
+        #[cfg_attr(feature = "diagnostics", source_code)]
+        source_code: String,
+        #[cfg_attr(feature = "diagnostics", label("set opened here"))]
+        span: Range<usize>,
+    },
+
+    /// A `[` was never closed by a matching `]`.
+    #[error("unterminated range starting at {span:?}")]
+    UnterminatedRange {
+        #[cfg_attr(feature = "diagnostics", source_code)]
+        source_code: String,
+        #[cfg_attr(feature = "diagnostics", label("range opened here"))]
+        span: Range<usize>,
+    },
+
+    /// An alphabetic range mixed uppercase and lowercase bounds, e.g. `[a-Z]`.
+    #[error("mixed uppercase with lowercase in alphabetic range '{fragment}'")]
+    MixedCaseRange {
+        fragment: String,
+        #[cfg_attr(feature = "diagnostics", source_code)]
+        source_code: String,
+        #[cfg_attr(feature = "diagnostics", label("mixed case here"))]
+        span: Range<usize>,
+    },
+
+    /// One side of a `[start-end]` range was empty, e.g. `[-3]`.
+    #[error("range bound cannot be empty at {span:?}")]
+    EmptyRangeBound {
+        #[cfg_attr(feature = "diagnostics", source_code)]
+        source_code: String,
+        #[cfg_attr(feature = "diagnostics", label("empty bound here"))]
+        span: Range<usize>,
+    },
+
+    /// A range token mixed characters that are neither all-numeric nor
+    /// all-alphabetic, e.g. `[a-1]`.
+    #[error("invalid characters in range token '{fragment}'")]
+    InvalidRangeChars {
+        fragment: String,
+        #[cfg_attr(feature = "diagnostics", source_code)]
+        source_code: String,
+        #[cfg_attr(feature = "diagnostics", label("invalid range here"))]
+        span: Range<usize>,
+    },
+}
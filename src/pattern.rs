@@ -1,98 +1,93 @@
-use anyhow::{bail, Result};
-use itertools::Itertools;
-use std::mem::take;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
-use super::token::Token;
+use super::error::PatternError;
+use super::token::{self, Token, Wildcard};
+use super::walk;
 
 #[derive(Debug, Clone)]
 pub struct Pattern<'a> {
     original: &'a str,
-    tokens: Vec<Token<'a>>,
+    root: Token<'a>,
+    options: Options,
 }
 
-#[derive(PartialEq)]
-enum State<'a> {
-    Plain,
-    InSet(Vec<&'a str>),
-    InRange(&'a str),
+/// Configuration for [`Pattern::parse_with`], threaded into the matching
+/// routines (`matches`/`is_match`/`walk`). Forward expansion via
+/// [`Pattern::iter`] is unaffected — it always produces the pattern's
+/// canonical-case strings.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Options {
+    /// Compare `{...}` alternatives and `[...]` alphabetic range bounds
+    /// case-insensitively, mirroring how the wax glob crate relaxes
+    /// matching on case-insensitive filesystems like Windows/macOS.
+    /// Case-folding is ASCII-only, consistent with the crate's
+    /// `to_alphabetic_radix`/`parse_alphabetic_radix` helpers only
+    /// understanding `a-z`/`A-Z`.
+    pub case_insensitive: bool,
 }
 
-impl<'a> Pattern<'a> {
-    pub fn parse(s: &str) -> Result<Pattern> {
-        let mut pattern = Pattern {
-            original: s,
-            tokens: Vec::new(),
-        };
-        let mut state = State::Plain;
-        let (mut i, mut j) = (0, 0); // segment start & end index in s
-        for (idx, char) in s.char_indices() {
-            let next_idx = idx + char.len_utf8();
-            match char {
-                '{' => match &mut state {
-                    State::Plain => {
-                        pattern.tokens.push(Token::new_plain(&s[i..j]));
-                        (i, j, state) = (next_idx, next_idx, State::InSet(Vec::new()));
-                    }
-                    _ => bail!("unexpected character '{{' at pos {}", idx),
-                },
-                '[' => match &mut state {
-                    State::Plain => {
-                        pattern.tokens.push(Token::new_plain(&s[i..j]));
-                        (i, j, state) = (next_idx, next_idx, State::InRange(""));
-                    }
-                    _ => bail!("unexpected character '[' at pos {}", idx),
-                },
-                '}' => match &mut state {
-                    State::InSet(v) => {
-                        let mut set = take(v);
-                        set.push((&s[i..j]).trim());
-                        pattern.tokens.push(Token::new_set(set));
-                        (i, j, state) = (next_idx, next_idx, State::Plain);
-                    }
-                    _ => bail!("unexpected character '}}' at pos {}", idx),
-                },
-                ']' => match &mut state {
-                    State::InRange(start) => {
-                        let end = &s[i..j].trim();
-                        let token = match (start.chars().next(), end.chars().next()) {
-                            (Some('A'..='Z' | 'a'..='z'), Some('A'..='Z' | 'a'..='z')) => {
-                                Token::new_str_range(start, end)?
-                            }
-                            (Some('0'..='9'), Some('0'..='9')) => {
-                                let padding = start.len().min(end.len());
-                                let (start, end) = (start.parse()?, end.parse()?);
-                                Token::new_num_range(start, end, padding)
-                            }
-                            _ => bail!("invalid characters in range token before pos {}", idx),
-                        };
-                        pattern.tokens.push(token);
-                        (i, j, state) = (next_idx, next_idx, State::Plain);
-                    }
-                    _ => bail!("unexpected character ']' at pos {}", idx),
-                },
-                ',' => match &mut state {
-                    State::Plain => j = next_idx,
-                    State::InSet(set) => {
-                        set.push((&s[i..j]).trim());
-                        (i, j) = (next_idx, next_idx);
-                    }
-                    _ => bail!("unexpected character ',' at pos {}", idx),
-                },
-                '-' => match &mut state {
-                    State::Plain | State::InSet(_) => j = next_idx,
-                    State::InRange(start) => {
-                        *start = s[i..j].trim();
-                        (i, j) = (next_idx, next_idx);
-                    }
-                },
-                _ => j = next_idx,
-            }
+/// Whether the sequence currently being parsed is a top-level pattern or one
+/// branch of a `{...}` group — branches stop at `,`/`}`, the top level
+/// doesn't.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SeqEnd {
+    Pattern,
+    Branch,
+}
+
+/// Cursor over a pattern's `char_indices`, shared by the recursive-descent
+/// parser functions below.
+struct Cursor<'a> {
+    s: &'a str,
+    chars: Vec<(usize, char)>,
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(s: &'a str) -> Self {
+        Cursor {
+            s,
+            chars: s.char_indices().collect(),
+            pos: 0,
         }
-        if j > i {
-            pattern.tokens.push(Token::Plain((&s[i..j]).trim()));
+    }
+
+    fn peek(&self) -> Option<(usize, char)> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn advance(&mut self) -> Option<(usize, char)> {
+        let item = self.peek();
+        if item.is_some() {
+            self.pos += 1;
         }
+        item
+    }
 
-        Ok(pattern)
+    /// Byte offset of the next unconsumed character, or `s.len()` at EOF.
+    fn byte_offset(&self) -> usize {
+        self.peek().map(|(i, _)| i).unwrap_or(self.s.len())
+    }
+}
+
+impl<'a> Pattern<'a> {
+    pub fn parse(s: &str) -> Result<Pattern, PatternError> {
+        Pattern::parse_with(s, Options::default())
+    }
+
+    /// Parse `s` with explicit matching [`Options`], e.g. to make
+    /// `matches`/`is_match`/`walk` case-insensitive.
+    pub fn parse_with(s: &str, options: Options) -> Result<Pattern, PatternError> {
+        let mut cursor = Cursor::new(s);
+        let root = parse_seq(&mut cursor, SeqEnd::Pattern)?;
+        Ok(Pattern {
+            original: s,
+            root,
+            options,
+        })
     }
 
     pub fn as_str(&self) -> &str {
@@ -100,71 +95,573 @@ impl<'a> Pattern<'a> {
     }
 
     pub fn iter(&self) -> impl Iterator<Item = String> + '_ {
-        self.tokens
-            .iter()
-            .map(|v| v.iter())
-            .multi_cartesian_product()
-            .map(|v| v.join(""))
+        self.root.iter()
+    }
+
+    /// Check whether `input` is one of the strings this pattern can
+    /// generate, and if so, return the value captured at each `{...}`
+    /// group and range — essentially the inverse of [`Pattern::iter`].
+    ///
+    /// A group captures the substring its chosen branch consumed (so a
+    /// plain alternative like `{a,b,c}` captures `"b"` directly, while a
+    /// branch containing its own groups or ranges, e.g. `{a,b{1,2}}`, also
+    /// surfaces those nested captures in left-to-right order).
+    pub fn matches<'b>(&self, input: &'b str) -> Option<Vec<Cow<'b, str>>> {
+        let cache = MatchCache::default();
+        node_candidates(&self.root, input, self.options, &cache)
+            .into_iter()
+            .find_map(|(len, caps)| (len == input.len()).then_some(caps))
+    }
+
+    /// Cheaper form of [`Pattern::matches`] for callers that only need a
+    /// yes/no answer and don't care which values were captured. Unlike
+    /// `matches`, this never builds a capture string — it backtracks over
+    /// consumed lengths only, via [`node_lens`] — so it skips the
+    /// allocation that finding out *what* matched would cost.
+    pub fn is_match(&self, input: &str) -> bool {
+        let cache = LenCache::default();
+        node_lens(&self.root, input, self.options, &cache).contains(&input.len())
+    }
+
+    /// Lazily walk `root` on disk, yielding every existing path that
+    /// matches this pattern. The pattern is split into path components on
+    /// top-level `/` characters (a `/` nested inside a `{...}` branch is
+    /// not treated as a separator); deterministic components — built from
+    /// plain text, `{...}` groups and ranges — are expanded directly via
+    /// [`Token::iter`], while components containing `?`, `*` or `**` are
+    /// matched against real directory entries instead, the same way
+    /// [`Pattern::matches`] matches a group. A lone `**` component may
+    /// match zero or more whole directories, so `a/**/b` also matches
+    /// `a/b`.
+    ///
+    /// Path components are compared via [`OsStr`](std::ffi::OsStr) rather
+    /// than forced through UTF-8, so a lone `*` also matches entries whose
+    /// name isn't valid UTF-8; any component with other tokens still needs
+    /// the entry name to decode so its characters can be compared.
+    pub fn walk(&self, root: impl AsRef<Path>) -> impl Iterator<Item = PathBuf> + '_ {
+        walk::walk(&self.root, root.as_ref(), self.options)
     }
 }
 
 impl<'a> TryFrom<&'a str> for Pattern<'a> {
-    type Error = anyhow::Error;
+    type Error = PatternError;
 
     fn try_from(s: &'a str) -> Result<Self, Self::Error> {
         Pattern::parse(s)
     }
 }
 
+fn flush_plain<'a>(nodes: &mut Vec<Token<'a>>, s: &'a str, start: usize, end: usize) {
+    if end > start {
+        nodes.push(Token::new_plain(s[start..end].trim()));
+    }
+}
+
+/// Parse a run of adjacent tokens: plain text, `{...}` groups, `[...]`
+/// ranges and `?`/`*`/`**` wildcards concatenate until `end` says to stop
+/// (or the input runs out). Wildcards are only recognised at the top level;
+/// inside a `{...}` branch `?` and `*` are literal characters. `**` is just
+/// two consecutive `*` collapsed into one token; `***` reads as `**`
+/// followed by `*`, and longer runs pair up the same way.
+fn parse_seq<'a>(cursor: &mut Cursor<'a>, end: SeqEnd) -> Result<Token<'a>, PatternError> {
+    let mut nodes = Vec::new();
+    let (mut lit_start, mut lit_end) = (cursor.byte_offset(), cursor.byte_offset());
+
+    loop {
+        match cursor.peek() {
+            None => break,
+            Some((idx, '{')) => {
+                flush_plain(&mut nodes, cursor.s, lit_start, lit_end);
+                cursor.advance();
+                nodes.push(parse_group(cursor, idx)?);
+                (lit_start, lit_end) = (cursor.byte_offset(), cursor.byte_offset());
+            }
+            Some((idx, '[')) => {
+                flush_plain(&mut nodes, cursor.s, lit_start, lit_end);
+                cursor.advance();
+                nodes.push(parse_range(cursor, idx)?);
+                (lit_start, lit_end) = (cursor.byte_offset(), cursor.byte_offset());
+            }
+            Some((_, '?')) if end == SeqEnd::Pattern => {
+                flush_plain(&mut nodes, cursor.s, lit_start, lit_end);
+                cursor.advance();
+                nodes.push(Token::new_wildcard(Wildcard::One));
+                (lit_start, lit_end) = (cursor.byte_offset(), cursor.byte_offset());
+            }
+            Some((_, '*')) if end == SeqEnd::Pattern => {
+                flush_plain(&mut nodes, cursor.s, lit_start, lit_end);
+                cursor.advance();
+                let kind = if matches!(cursor.peek(), Some((_, '*'))) {
+                    cursor.advance();
+                    // `**` owns its trailing separator, so `a/**/b` means
+                    // "zero or more whole components between a/ and b",
+                    // rather than leaving a dangling `/` that zero matched
+                    // components could never satisfy.
+                    if matches!(cursor.peek(), Some((_, '/'))) {
+                        cursor.advance();
+                    }
+                    Wildcard::Tree
+                } else {
+                    Wildcard::Many
+                };
+                nodes.push(Token::new_wildcard(kind));
+                (lit_start, lit_end) = (cursor.byte_offset(), cursor.byte_offset());
+            }
+            Some((_, '}' | ',')) if end == SeqEnd::Branch => break,
+            Some((idx, c @ ('}' | ']'))) => {
+                return Err(PatternError::UnexpectedChar {
+                    found: c,
+                    source_code: cursor.s.to_string(),
+                    span: idx..idx + c.len_utf8(),
+                })
+            }
+            Some((_, c)) => {
+                cursor.advance();
+                lit_end += c.len_utf8();
+            }
+        }
+    }
+    flush_plain(&mut nodes, cursor.s, lit_start, lit_end);
+
+    Ok(match nodes.len() {
+        0 => Token::new_plain(""),
+        1 => nodes.into_iter().next().unwrap(),
+        _ => Token::new_seq(nodes),
+    })
+}
+
+/// Parse the comma-separated branches of a `{...}` group, having already
+/// consumed the opening `{` at `open_at`.
+fn parse_group<'a>(cursor: &mut Cursor<'a>, open_at: usize) -> Result<Token<'a>, PatternError> {
+    let mut branches = Vec::new();
+    loop {
+        branches.push(parse_seq(cursor, SeqEnd::Branch)?);
+        match cursor.advance() {
+            Some((_, ',')) => continue,
+            Some((_, '}')) => break,
+            _ => {
+                return Err(PatternError::UnterminatedSet {
+                    source_code: cursor.s.to_string(),
+                    span: open_at..cursor.s.len(),
+                })
+            }
+        }
+    }
+    Ok(Token::new_alt(branches))
+}
+
+/// Parse the `start-end` body of a `[...]` range, having already consumed
+/// the opening `[` at `open_at`.
+fn parse_range<'a>(cursor: &mut Cursor<'a>, open_at: usize) -> Result<Token<'a>, PatternError> {
+    let mut start: Option<&'a str> = None;
+    let (mut seg_start, mut seg_end) = (cursor.byte_offset(), cursor.byte_offset());
+
+    loop {
+        match cursor.advance() {
+            None => {
+                return Err(PatternError::UnterminatedRange {
+                    source_code: cursor.s.to_string(),
+                    span: open_at..cursor.s.len(),
+                })
+            }
+            Some((_, '-')) => {
+                start = Some(cursor.s[seg_start..seg_end].trim());
+                (seg_start, seg_end) = (cursor.byte_offset(), cursor.byte_offset());
+            }
+            Some((idx, ']')) => {
+                let end = cursor.s[seg_start..seg_end].trim();
+                let span = open_at..idx + 1;
+                return Token::new_range(start.unwrap_or(""), end, span, cursor.s);
+            }
+            Some((idx, c @ ('{' | '}' | '['))) => {
+                return Err(PatternError::UnexpectedChar {
+                    found: c,
+                    source_code: cursor.s.to_string(),
+                    span: idx..idx + c.len_utf8(),
+                })
+            }
+            Some((_, c)) => seg_end += c.len_utf8(),
+        }
+    }
+}
+
+/// Per-call memo for [`node_candidates`]/[`seq_candidates`], keyed on a
+/// node's identity together with how much of the (fixed, shared) input
+/// string remains. Since every recursive call only ever hands down a
+/// suffix of the original `input`, remaining-length alone pins down which
+/// suffix it is, so `(node address, input.len())` is a sound cache key for
+/// the lifetime of one top-level call. Must be created fresh per
+/// top-level call — it must never outlive the `input` it was built for,
+/// since a cached `(ptr, len)` pair is only meaningful relative to that
+/// one string.
+///
+/// Memoizing alone isn't enough: an `Alt` with several branches that
+/// legitimately match the same length (duplicate literals, or two
+/// branches that collide under `case_insensitive`) makes its result
+/// vector itself combinatorial, and `seq_candidates` multiplies that size
+/// across every sibling. `dedup_candidates` collapses equal `(len,
+/// captures)` pairs before they're cached, so a chain of N ambiguous
+/// groups stays O(N) candidates instead of O(2^N).
+#[derive(Default)]
+struct MatchCache<'b> {
+    node: RefCell<HashMap<(usize, usize), Vec<(usize, Vec<Cow<'b, str>>)>>>,
+    seq: RefCell<HashMap<(usize, usize, usize), Vec<(usize, Vec<Cow<'b, str>>)>>>,
+}
+
+/// Collapse candidates that consumed the same length and captured the
+/// same values down to one — see [`MatchCache`] for why this is load
+/// bearing, not just tidying.
+fn dedup_candidates<'b>(mut candidates: Vec<(usize, Vec<Cow<'b, str>>)>) -> Vec<(usize, Vec<Cow<'b, str>>)> {
+    candidates.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+    candidates.dedup();
+    candidates
+}
+
+/// Enumerate every way `token` can consume a prefix of `input`, returning
+/// the consumed length together with the captures that choice contributes.
+/// `Plain` and `Seq` never capture themselves (they're structural); `Alt`
+/// captures the substring its branch consumed, and range leaves capture
+/// their resolved value. `options.case_insensitive` relaxes `Plain` and
+/// `StrRange` comparisons; captures always preserve `input`'s original
+/// case.
+///
+/// An `Alt` branch's capture is a literal substring of `input`, so it
+/// borrows rather than allocates. A range's capture is also the substring
+/// it consumed, but `match_num_range`/`match_str_range` return it as an
+/// owned `String`, so it stays owned.
+fn node_candidates<'b>(
+    token: &Token<'_>,
+    input: &'b str,
+    options: Options,
+    cache: &MatchCache<'b>,
+) -> Vec<(usize, Vec<Cow<'b, str>>)> {
+    let key = (token as *const Token<'_> as usize, input.len());
+    if let Some(hit) = cache.node.borrow().get(&key) {
+        return hit.clone();
+    }
+
+    let result = match token {
+        Token::Plain(s) => {
+            let matched = if options.case_insensitive {
+                input.get(..s.len()).is_some_and(|p| p.eq_ignore_ascii_case(s))
+            } else {
+                input.starts_with(s)
+            };
+            if matched {
+                vec![(s.len(), Vec::new())]
+            } else {
+                Vec::new()
+            }
+        }
+        &Token::NumRange(start, end, padding) => token::match_num_range(input, start, end, padding)
+            .into_iter()
+            .map(|(value, len)| (len, vec![Cow::Owned(value)]))
+            .collect(),
+        &Token::StrRange(start, end, uppercase) => {
+            token::match_str_range(input, start, end, uppercase, options.case_insensitive)
+                .into_iter()
+                .map(|(value, len)| (len, vec![Cow::Owned(value)]))
+                .collect()
+        }
+        Token::Wildcard(kind) => wildcard_candidates(*kind, input),
+        Token::Alt(branches) => branches
+            .iter()
+            .flat_map(|branch| {
+                node_candidates(branch, input, options, cache)
+                    .into_iter()
+                    .map(|(len, mut caps)| {
+                        let mut result = vec![Cow::Borrowed(&input[..len])];
+                        result.append(&mut caps);
+                        (len, result)
+                    })
+            })
+            .collect(),
+        Token::Seq(children) => seq_candidates(children, input, options, cache),
+    };
+    let result = dedup_candidates(result);
+
+    cache.node.borrow_mut().insert(key, result.clone());
+    result
+}
+
+/// Per-call memo for [`node_lens`]/[`seq_lens`], the same shape as
+/// [`MatchCache`] but keyed purely on consumed length — see that type's
+/// doc comment for why `(node address, input.len())` is a sound key.
+#[derive(Default)]
+struct LenCache {
+    node: RefCell<HashMap<(usize, usize), Vec<usize>>>,
+    seq: RefCell<HashMap<(usize, usize, usize), Vec<usize>>>,
+}
+
+/// Enumerate the distinct lengths `token` can consume a prefix of `input`
+/// by, without building any capture — the boolean-only counterpart to
+/// [`node_candidates`], used by [`Pattern::is_match`]. Dropping captures
+/// means there's nothing left to distinguish two branches that consume
+/// the same length, so results are deduplicated to at most
+/// `input.len() + 1` entries per node (`node_candidates` only merges
+/// candidates whose captures are equal too), without ever allocating a
+/// capture string in the first place.
+fn node_lens(token: &Token<'_>, input: &str, options: Options, cache: &LenCache) -> Vec<usize> {
+    let key = (token as *const Token<'_> as usize, input.len());
+    if let Some(hit) = cache.node.borrow().get(&key) {
+        return hit.clone();
+    }
+
+    let mut result = match token {
+        Token::Plain(s) => {
+            let matched = if options.case_insensitive {
+                input.get(..s.len()).is_some_and(|p| p.eq_ignore_ascii_case(s))
+            } else {
+                input.starts_with(s)
+            };
+            if matched {
+                vec![s.len()]
+            } else {
+                Vec::new()
+            }
+        }
+        &Token::NumRange(start, end, padding) => token::match_num_range(input, start, end, padding)
+            .into_iter()
+            .map(|(_, len)| len)
+            .collect(),
+        &Token::StrRange(start, end, uppercase) => {
+            token::match_str_range(input, start, end, uppercase, options.case_insensitive)
+                .into_iter()
+                .map(|(_, len)| len)
+                .collect()
+        }
+        Token::Wildcard(kind) => wildcard_candidates(*kind, input)
+            .into_iter()
+            .map(|(len, _)| len)
+            .collect(),
+        Token::Alt(branches) => branches
+            .iter()
+            .flat_map(|branch| node_lens(branch, input, options, cache))
+            .collect(),
+        Token::Seq(children) => seq_lens(children, input, options, cache),
+    };
+    result.sort_unstable();
+    result.dedup();
+
+    cache.node.borrow_mut().insert(key, result.clone());
+    result
+}
+
+fn seq_lens(children: &[Token<'_>], input: &str, options: Options, cache: &LenCache) -> Vec<usize> {
+    let Some((first, rest)) = children.split_first() else {
+        return vec![0];
+    };
+
+    let key = (children.as_ptr() as usize, children.len(), input.len());
+    if let Some(hit) = cache.seq.borrow().get(&key) {
+        return hit.clone();
+    }
+
+    let mut result: Vec<usize> = node_lens(first, input, options, cache)
+        .into_iter()
+        .flat_map(|len| {
+            seq_lens(rest, &input[len..], options, cache)
+                .into_iter()
+                .map(move |rest_len| len + rest_len)
+        })
+        .collect();
+    result.sort_unstable();
+    result.dedup();
+
+    cache.seq.borrow_mut().insert(key, result.clone());
+    result
+}
+
+/// Enumerate the ways a wildcard can consume a prefix of `input`, treating
+/// `/` as the path separator. `One` consumes exactly one non-separator
+/// character; `Many` backtracks over every length of the leading
+/// separator-free run (including zero); `Tree` backtracks over every prefix
+/// that ends right after a `/` (a whole number of path components), plus
+/// the entire remaining input, so it can also match inside the last
+/// component.
+fn wildcard_candidates<'b>(kind: Wildcard, input: &str) -> Vec<(usize, Vec<Cow<'b, str>>)> {
+    match kind {
+        Wildcard::One => input
+            .chars()
+            .next()
+            .filter(|&c| c != '/')
+            .map(|c| vec![(c.len_utf8(), Vec::new())])
+            .unwrap_or_default(),
+        Wildcard::Many => {
+            let mut lens = vec![0];
+            let mut offset = 0;
+            for c in input.chars() {
+                if c == '/' {
+                    break;
+                }
+                offset += c.len_utf8();
+                lens.push(offset);
+            }
+            lens.into_iter().map(|len| (len, Vec::new())).collect()
+        }
+        Wildcard::Tree => {
+            let mut lens = vec![0];
+            for (idx, c) in input.char_indices() {
+                if c == '/' {
+                    lens.push(idx + 1);
+                }
+            }
+            if !lens.contains(&input.len()) {
+                lens.push(input.len());
+            }
+            lens.into_iter().map(|len| (len, Vec::new())).collect()
+        }
+    }
+}
+
+/// Whether `children` (a single path component's tokens, per
+/// [`Pattern::walk`]) fully consume `input`. Shares the same backtracking
+/// engine as [`Pattern::matches`]; the captures it collects are discarded.
+pub(crate) fn matches_component(children: &[Token<'_>], input: &str, options: Options) -> bool {
+    let cache = MatchCache::default();
+    seq_candidates(children, input, options, &cache)
+        .into_iter()
+        .any(|(len, _)| len == input.len())
+}
+
+fn seq_candidates<'b>(
+    children: &[Token<'_>],
+    input: &'b str,
+    options: Options,
+    cache: &MatchCache<'b>,
+) -> Vec<(usize, Vec<Cow<'b, str>>)> {
+    let Some((first, rest)) = children.split_first() else {
+        return vec![(0, Vec::new())];
+    };
+
+    let key = (children.as_ptr() as usize, children.len(), input.len());
+    if let Some(hit) = cache.seq.borrow().get(&key) {
+        return hit.clone();
+    }
+
+    let result = node_candidates(first, input, options, cache)
+        .into_iter()
+        .flat_map(|(len, caps)| {
+            seq_candidates(rest, &input[len..], options, cache)
+                .into_iter()
+                .map(move |(rest_len, rest_caps)| {
+                    let mut combined = caps.clone();
+                    combined.extend(rest_caps);
+                    (len + rest_len, combined)
+                })
+        })
+        .collect::<Vec<_>>();
+    let result = dedup_candidates(result);
+
+    cache.seq.borrow_mut().insert(key, result.clone());
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
-    fn test_parse() -> Result<()> {
+    fn test_parse() -> Result<(), PatternError> {
         let cases = vec![
-            (
-                "normal",
-                "Hello, World!",
-                vec![Token::new_plain("Hello, World!")],
-            ),
+            ("normal", "Hello, World!", Token::new_plain("Hello, World!")),
             (
                 "one set",
                 "https://example.com/{a,b,c}/file",
-                vec![
+                Token::new_seq(vec![
                     Token::new_plain("https://example.com/"),
-                    Token::new_set(vec!["a", "b", "c"]),
+                    Token::new_alt(vec![
+                        Token::new_plain("a"),
+                        Token::new_plain("b"),
+                        Token::new_plain("c"),
+                    ]),
                     Token::new_plain("/file"),
-                ],
+                ]),
             ),
             (
                 "two set",
                 "https://example.com/{a,b,c}/file/{x,y,z}",
-                vec![
+                Token::new_seq(vec![
                     Token::new_plain("https://example.com/"),
-                    Token::new_set(vec!["a", "b", "c"]),
+                    Token::new_alt(vec![
+                        Token::new_plain("a"),
+                        Token::new_plain("b"),
+                        Token::new_plain("c"),
+                    ]),
                     Token::new_plain("/file/"),
-                    Token::new_set(vec!["x", "y", "z"]),
-                ],
+                    Token::new_alt(vec![
+                        Token::new_plain("x"),
+                        Token::new_plain("y"),
+                        Token::new_plain("z"),
+                    ]),
+                ]),
             ),
             (
                 "two set with spaces",
                 "https://example.com/{a, b , c }/file/{foo bar, 你好, fizzbuzz, 世界}",
-                vec![
+                Token::new_seq(vec![
                     Token::new_plain("https://example.com/"),
-                    Token::new_set(vec!["a", "b", "c"]),
+                    Token::new_alt(vec![
+                        Token::new_plain("a"),
+                        Token::new_plain("b"),
+                        Token::new_plain("c"),
+                    ]),
                     Token::new_plain("/file/"),
-                    Token::new_set(vec!["foo bar", "你好", "fizzbuzz", "世界"]),
-                ],
+                    Token::new_alt(vec![
+                        Token::new_plain("foo bar"),
+                        Token::new_plain("你好"),
+                        Token::new_plain("fizzbuzz"),
+                        Token::new_plain("世界"),
+                    ]),
+                ]),
             ),
             (
                 "one number range",
                 "https://example.com/[080-120]/file",
-                vec![
+                Token::new_seq(vec![
                     Token::new_plain("https://example.com/"),
                     Token::new_num_range(80, 120, 3),
                     Token::new_plain("/file"),
-                ],
+                ]),
+            ),
+            (
+                "nested group",
+                "img-{a,b{1,2},c}.png",
+                Token::new_seq(vec![
+                    Token::new_plain("img-"),
+                    Token::new_alt(vec![
+                        Token::new_plain("a"),
+                        Token::new_seq(vec![
+                            Token::new_plain("b"),
+                            Token::new_alt(vec![Token::new_plain("1"), Token::new_plain("2")]),
+                        ]),
+                        Token::new_plain("c"),
+                    ]),
+                    Token::new_plain(".png"),
+                ]),
+            ),
+            (
+                "group and range side by side",
+                "{lo,hi}-[0-9]",
+                Token::new_seq(vec![
+                    Token::new_alt(vec![Token::new_plain("lo"), Token::new_plain("hi")]),
+                    Token::new_plain("-"),
+                    Token::new_num_range(0, 9, 1),
+                ]),
+            ),
+            (
+                "wildcards",
+                "logs/**/file-?.[0-9].*",
+                Token::new_seq(vec![
+                    Token::new_plain("logs/"),
+                    Token::new_wildcard(Wildcard::Tree),
+                    Token::new_plain("file-"),
+                    Token::new_wildcard(Wildcard::One),
+                    Token::new_plain("."),
+                    Token::new_num_range(0, 9, 1),
+                    Token::new_plain("."),
+                    Token::new_wildcard(Wildcard::Many),
+                ]),
             ),
         ];
 
@@ -172,40 +669,58 @@ mod tests {
             let p = Pattern::parse(input)?;
 
             assert_eq!(p.original, input, "case {name}");
-            assert_eq!(p.tokens, expected, "case {name}");
+            assert_eq!(p.root, expected, "case {name}");
         }
 
         Ok(())
     }
 
     #[test]
-    fn test_parse_error() -> Result<()> {
+    fn test_parse_error() {
         let cases = vec![
             (
-                "bad pattern 1",
-                "/{{a, b}",
-                "unexpected character '{' at pos 2",
+                "unexpected close brace",
+                "/{a}}",
+                PatternError::UnexpectedChar {
+                    found: '}',
+                    source_code: "/{a}}".to_string(),
+                    span: 4..5,
+                },
             ),
             (
-                "bad pattern 2",
-                "/{a}}",
-                "unexpected character '}' at pos 4",
+                "unterminated set",
+                "/{a,b",
+                PatternError::UnterminatedSet {
+                    source_code: "/{a,b".to_string(),
+                    span: 1..5,
+                },
+            ),
+            (
+                "unterminated range",
+                "/[0-9",
+                PatternError::UnterminatedRange {
+                    source_code: "/[0-9".to_string(),
+                    span: 1..5,
+                },
+            ),
+            (
+                "invalid range chars",
+                "/[a-9]",
+                PatternError::InvalidRangeChars {
+                    fragment: "a-9".to_string(),
+                    source_code: "/[a-9]".to_string(),
+                    span: 1..6,
+                },
             ),
         ];
 
         for (name, input, expected) in cases {
-            assert_eq!(
-                Pattern::parse(input).unwrap_err().to_string(),
-                expected,
-                "case {name}"
-            )
+            assert_eq!(Pattern::parse(input).unwrap_err(), expected, "case {name}");
         }
-
-        Ok(())
     }
 
     #[test]
-    fn test_iter() -> Result<()> {
+    fn test_iter() -> Result<(), PatternError> {
         let cases = vec![
             ("normal", "Hello, World!", vec!["Hello, World!"]),
             (
@@ -241,6 +756,15 @@ mod tests {
                     "https://example.com/3/file",
                 ],
             ),
+            (
+                "single letter range",
+                "https://example.com/[a-c]/file",
+                vec![
+                    "https://example.com/a/file",
+                    "https://example.com/b/file",
+                    "https://example.com/c/file",
+                ],
+            ),
             (
                 "two number range with padding zero",
                 "https://example.com/[1-2]/file/[099-101]",
@@ -253,15 +777,6 @@ mod tests {
                     "https://example.com/2/file/101",
                 ],
             ),
-            (
-                "single letter range",
-                "https://example.com/[A-C]/file",
-                vec![
-                    "https://example.com/A/file",
-                    "https://example.com/B/file",
-                    "https://example.com/C/file",
-                ],
-            ),
             (
                 "multi letters range",
                 "https://example.com/[ay-bc]/file",
@@ -273,6 +788,24 @@ mod tests {
                     "https://example.com/bc/file",
                 ],
             ),
+            (
+                "nested group",
+                "img-{a,b{1,2},c}.png",
+                vec![
+                    "img-a.png",
+                    "img-b1.png",
+                    "img-b2.png",
+                    "img-c.png",
+                ],
+            ),
+            (
+                "group and range side by side",
+                "{lo,hi}-[0-9]",
+                vec![
+                    "lo-0", "lo-1", "lo-2", "lo-3", "lo-4", "lo-5", "lo-6", "lo-7", "lo-8", "lo-9",
+                    "hi-0", "hi-1", "hi-2", "hi-3", "hi-4", "hi-5", "hi-6", "hi-7", "hi-8", "hi-9",
+                ],
+            ),
         ];
 
         for (name, input, expected) in cases {
@@ -283,4 +816,135 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_matches() -> Result<(), PatternError> {
+        let cases = vec![
+            ("plain", "Hello, World!", "Hello, World!", Some(vec![])),
+            ("plain mismatch", "Hello, World!", "Goodbye, World!", None),
+            (
+                "one set",
+                "https://example.com/{a,b,c}/file",
+                "https://example.com/b/file",
+                Some(vec![Cow::Borrowed("b")]),
+            ),
+            (
+                "set value not in set",
+                "https://example.com/{a,b,c}/file",
+                "https://example.com/d/file",
+                None,
+            ),
+            (
+                "number range with padding",
+                "https://example.com/[080-120]/file",
+                "https://example.com/099/file",
+                Some(vec![Cow::Borrowed("099")]),
+            ),
+            (
+                "number range out of bounds",
+                "https://example.com/[080-120]/file",
+                "https://example.com/200/file",
+                None,
+            ),
+            (
+                "two sets captured in order",
+                "https://example.com/{a,b,c}/file/{x,y,z}",
+                "https://example.com/c/file/y",
+                Some(vec![Cow::Borrowed("c"), Cow::Borrowed("y")]),
+            ),
+            (
+                "nested group captures branch and inner range",
+                "img-{a,b[1-2],c}.png",
+                "img-b2.png",
+                Some(vec![Cow::Borrowed("b2"), Cow::Borrowed("2")]),
+            ),
+            ("single char wildcard", "file-?.log", "file-3.log", Some(vec![])),
+            (
+                "single char wildcard does not cross separator",
+                "file-?.log",
+                "file-/.log",
+                None,
+            ),
+            (
+                "many wildcard within a component",
+                "logs/*.log",
+                "logs/app-2024.log",
+                Some(vec![]),
+            ),
+            (
+                "many wildcard does not cross separator",
+                "logs/*.log",
+                "logs/a/b.log",
+                None,
+            ),
+            (
+                "tree wildcard matches any depth",
+                "logs/**/*.log",
+                "logs/2024/07/27/app.log",
+                Some(vec![]),
+            ),
+            (
+                "tree wildcard matches zero components",
+                "logs/**/*.log",
+                "logs/app.log",
+                Some(vec![]),
+            ),
+        ];
+
+        for (name, pattern, input, expected) in cases {
+            let p = Pattern::parse(pattern)?;
+
+            assert_eq!(p.matches(input), expected, "case {name}");
+            assert_eq!(p.is_match(input), expected.is_some(), "case {name}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_matches_case_insensitive() -> Result<(), PatternError> {
+        let options = Options {
+            case_insensitive: true,
+        };
+
+        let p = Pattern::parse_with("{img,doc}/[a-z]", options)?;
+        assert_eq!(
+            p.matches("IMG/B"),
+            Some(vec![Cow::Borrowed("IMG"), Cow::Borrowed("B")])
+        );
+
+        let default = Pattern::parse("{img,doc}/[a-z]")?;
+        assert_eq!(default.matches("IMG/B"), None, "default stays case-sensitive");
+
+        Ok(())
+    }
+
+    /// A long chain of `{a,a}` groups gives every `Alt` two branches that
+    /// match the same length with the same capture, which used to make
+    /// `node_candidates`/`seq_candidates` multiply that ambiguity across
+    /// every sibling — this used to take seconds (and OOM at larger
+    /// repeat counts) before candidates were deduplicated.
+    #[test]
+    fn test_matches_ambiguous_alternation_stays_bounded() -> Result<(), PatternError> {
+        let pattern = "{a,a}".repeat(20);
+        let input = "a".repeat(20);
+        let p = Pattern::parse(&pattern)?;
+
+        assert_eq!(p.matches(&input).map(|caps| caps.len()), Some(20));
+        Ok(())
+    }
+
+    /// `is_match` backtracks over consumed lengths only (no captures), so
+    /// it needs its own regression case to confirm it stays bounded on
+    /// the same ambiguous input as `matches`.
+    #[test]
+    fn test_is_match_ambiguous_alternation_stays_bounded() -> Result<(), PatternError> {
+        let pattern = "{a,a}".repeat(20);
+        let input = "a".repeat(20);
+        let p = Pattern::parse(&pattern)?;
+
+        assert!(p.is_match(&input));
+        assert!(!p.is_match(&"a".repeat(19)));
+        Ok(())
+    }
 }
@@ -1,87 +1,248 @@
-use std::{borrow::Cow, ops::RangeInclusive, slice::Iter};
+use std::ops::{Range, RangeInclusive};
 
-use anyhow::{bail, Result};
+use itertools::Itertools;
 
+use crate::error::PatternError;
+
+/// A node in the pattern's expansion tree.
+///
+/// `{...}` groups parse into `Alt`, whose branches are themselves full
+/// sub-trees (so groups can nest and contain further groups or ranges).
+/// Adjacent siblings — the literal text around and inside a group —
+/// concatenate as `Seq`. `Plain`, the two range variants and `Wildcard` are
+/// the leaves.
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token<'a> {
     Plain(&'a str),
-    Set(Vec<&'a str>),
+    /// A `{a,b,c}` group: exactly one branch is selected per expansion.
+    Alt(Vec<Token<'a>>),
+    /// Adjacent tokens that concatenate, e.g. `b{1,2}` inside a group.
+    Seq(Vec<Token<'a>>),
     NumRange(usize, usize, usize /* padding width */),
     StrRange(usize, usize, bool /* uppercase */),
+    /// A `?`, `*` or `**` glob wildcard, parsed outside `{}`/`[]` contexts.
+    /// Unlike the other leaves it has no finite expansion — it's only
+    /// meaningful when matched against real path components, via
+    /// [`Pattern::walk`](crate::pattern::Pattern::walk).
+    Wildcard(Wildcard),
+}
+
+/// The three wildcard forms `Pattern::walk` understands, mirroring shell
+/// glob syntax.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wildcard {
+    /// `?`: exactly one character that isn't a path separator.
+    One,
+    /// `*`: zero or more characters within a single path component.
+    Many,
+    /// `**`: zero or more whole path components.
+    Tree,
+}
+
+impl Wildcard {
+    /// The literal glyph this wildcard was parsed from.
+    fn as_str(self) -> &'static str {
+        match self {
+            Wildcard::One => "?",
+            Wildcard::Many => "*",
+            Wildcard::Tree => "**",
+        }
+    }
 }
 
-impl<'a> Token<'_> {
+impl<'a> Token<'a> {
     pub fn new_plain(s: impl Into<&'a str>) -> Token<'a> {
         Token::Plain(s.into())
     }
 
-    pub fn new_set(s: impl Into<Vec<&'a str>>) -> Token<'a> {
-        Token::Set(s.into())
+    pub fn new_alt(v: impl Into<Vec<Token<'a>>>) -> Token<'a> {
+        Token::Alt(v.into())
+    }
+
+    pub fn new_seq(v: impl Into<Vec<Token<'a>>>) -> Token<'a> {
+        Token::Seq(v.into())
     }
 
     pub fn new_num_range(start: usize, end: usize, padding: usize) -> Token<'a> {
         Token::NumRange(start, end, padding)
     }
 
-    pub fn new_str_range(start: &'a str, end: &'a str) -> Result<Token<'a>> {
+    pub fn new_wildcard(kind: Wildcard) -> Token<'a> {
+        Token::Wildcard(kind)
+    }
+
+    /// Whether this token (or, for `Alt`/`Seq`, any of its descendants) is a
+    /// wildcard. Wildcards have no finite expansion, so `Pattern::walk` uses
+    /// this to tell a path component it can enumerate directly from one it
+    /// must match against real directory entries instead.
+    pub fn contains_wildcard(&self) -> bool {
+        match self {
+            Token::Wildcard(_) => true,
+            Token::Alt(children) | Token::Seq(children) => {
+                children.iter().any(Token::contains_wildcard)
+            }
+            Token::Plain(_) | Token::NumRange(..) | Token::StrRange(..) => false,
+        }
+    }
+
+    /// Build a numeric or alphabetic range token from the trimmed
+    /// `start`/`end` bounds of a `[...]` range. `span`/`source_code` locate
+    /// the whole range token in the original pattern, for error reporting.
+    pub fn new_range(
+        start: &str,
+        end: &str,
+        span: Range<usize>,
+        source_code: &str,
+    ) -> Result<Token<'a>, PatternError> {
         match (start.chars().next(), end.chars().next()) {
-            (Some(c1), Some(c2)) => {
+            (Some('0'..='9'), Some('0'..='9')) => {
+                let padding = start.len().min(end.len());
+                match (start.parse(), end.parse()) {
+                    (Ok(start), Ok(end)) => Ok(Token::NumRange(start, end, padding)),
+                    _ => Err(PatternError::InvalidRangeChars {
+                        fragment: format!("{start}-{end}"),
+                        source_code: source_code.to_string(),
+                        span,
+                    }),
+                }
+            }
+            (Some(c1), Some(c2)) if c1.is_ascii_alphabetic() && c2.is_ascii_alphabetic() => {
                 let (uppercase, radix) = match (c1.is_ascii_uppercase(), c2.is_ascii_uppercase()) {
                     (false, false) => (false, 'a'..='z'),
                     (true, true) => (true, 'A'..='Z'),
-                    _ => bail!("mixed uppercase with lowercase in alphabetic range"),
+                    _ => {
+                        return Err(PatternError::MixedCaseRange {
+                            fragment: format!("{start}-{end}"),
+                            source_code: source_code.to_string(),
+                            span,
+                        })
+                    }
+                };
+                let invalid = |fragment: &str| PatternError::InvalidRangeChars {
+                    fragment: fragment.to_string(),
+                    source_code: source_code.to_string(),
+                    span: span.clone(),
                 };
                 Ok(Token::StrRange(
-                    parse_alphabetic_radix(start, radix.clone())?,
-                    parse_alphabetic_radix(end, radix)?,
+                    parse_alphabetic_radix(start, radix.clone()).ok_or_else(|| invalid(start))?,
+                    parse_alphabetic_radix(end, radix).ok_or_else(|| invalid(end))?,
                     uppercase,
                 ))
             }
-            (None, _) => bail!("range start cannot be empty"),
-            (_, None) => bail!("range end cannot be empty"),
+            (Some(_), Some(_)) => Err(PatternError::InvalidRangeChars {
+                fragment: format!("{start}-{end}"),
+                source_code: source_code.to_string(),
+                span,
+            }),
+            (None, _) | (_, None) => Err(PatternError::EmptyRangeBound {
+                source_code: source_code.to_string(),
+                span,
+            }),
         }
     }
 
-    pub fn iter(&self) -> TokenIter<'_> {
-        TokenIter::new(self)
+    /// Expand this token into every string it can produce, bottom-up:
+    /// `Plain` yields itself, `Alt` chains its branches' expansions, `Seq`
+    /// takes the cartesian product of its children, and the range variants
+    /// count through their bounds. `Wildcard` has no finite expansion, so it
+    /// yields its own glyph (`"?"`, `"*"` or `"**"`) unchanged — use
+    /// `Pattern::walk` instead of `iter()` for patterns containing one.
+    pub fn iter(&self) -> Box<dyn Iterator<Item = String> + '_> {
+        match self {
+            Token::Plain(s) => Box::new(std::iter::once((*s).to_string())),
+            &Token::NumRange(start, end, padding) => {
+                Box::new((start..=end).map(move |x| format!("{x:0padding$}")))
+            }
+            &Token::StrRange(start, end, uppercase) => {
+                let radix = if uppercase { 'A'..='Z' } else { 'a'..='z' };
+                Box::new((start..=end).map(move |x| to_alphabetic_radix(x, radix.clone())))
+            }
+            Token::Alt(branches) => Box::new(branches.iter().flat_map(|b| b.iter())),
+            Token::Wildcard(kind) => Box::new(std::iter::once(kind.as_str().to_string())),
+            Token::Seq(children) => {
+                // `multi_cartesian_product` requires each iterator to be
+                // `Clone` to replay it for every combination; the boxed
+                // trait object `c.iter()` returns isn't, so collect each
+                // child's (necessarily finite) expansion into an owned
+                // `Vec<String>` first.
+                let expansions: Vec<Vec<String>> =
+                    children.iter().map(|c| c.iter().collect()).collect();
+                Box::new(
+                    expansions
+                        .into_iter()
+                        .multi_cartesian_product()
+                        .map(|parts| parts.concat()),
+                )
+            }
+        }
     }
 }
 
-#[derive(Debug, Clone)]
-pub enum TokenIter<'a> {
-    Plain(Option<&'a str>),
-    Set(Iter<'a, &'a str>),
-    NumRange(RangeInclusive<usize>, usize /* padding width */),
-    StrRange(RangeInclusive<usize>, bool /* uppercase */),
+/// Enumerate the ways a `NumRange(start, end, padding)` leaf can consume a
+/// prefix of `input`, returning the formatted value together with the
+/// number of bytes consumed. Several lengths can be valid: a value with more
+/// digits than `padding` still matches at its natural width.
+pub(crate) fn match_num_range(
+    input: &str,
+    start: usize,
+    end: usize,
+    padding: usize,
+) -> Vec<(String, usize)> {
+    let digits = input.bytes().take_while(u8::is_ascii_digit).count();
+    (1..=digits)
+        .filter_map(|len| {
+            let candidate = &input[..len];
+            let value: usize = candidate.parse().ok()?;
+            let formatted = format!("{value:0padding$}");
+            ((start..=end).contains(&value) && formatted == candidate)
+                .then_some((formatted, len))
+        })
+        .collect()
 }
 
-impl<'a> TokenIter<'a> {
-    pub fn new(t: &'a Token) -> Self {
-        match t {
-            Token::Plain(v) => TokenIter::Plain(Some(v)),
-            Token::Set(v) => TokenIter::Set(v.iter()),
-            &Token::NumRange(start, end, padding) => TokenIter::NumRange(start..=end, padding),
-            &Token::StrRange(start, end, uppercase) => TokenIter::StrRange(start..=end, uppercase),
+/// Enumerate the ways a `StrRange(start, end, uppercase)` leaf can consume a
+/// prefix of `input`, returning the matched substring together with the
+/// number of bytes consumed. When `case_insensitive` is set, `input` may be
+/// either case — it's ASCII-folded to `uppercase`'s case before being
+/// parsed, but the captured substring still preserves whatever case
+/// `input` was actually written in.
+pub(crate) fn match_str_range(
+    input: &str,
+    start: usize,
+    end: usize,
+    uppercase: bool,
+    case_insensitive: bool,
+) -> Vec<(String, usize)> {
+    let radix = if uppercase { 'A'..='Z' } else { 'a'..='z' };
+    let accepts = |c: char| {
+        if case_insensitive {
+            c.is_ascii_alphabetic()
+        } else {
+            radix.contains(&c)
         }
-    }
-}
-
-impl<'a> Iterator for TokenIter<'a> {
-    type Item = Cow<'a, str>;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        match self {
-            TokenIter::Plain(v) => v.take().map(|v| v.into()),
-            TokenIter::Set(v) => v.next().map(|&v| v.into()),
-            TokenIter::NumRange(range, padding) => {
-                range.next().map(|x| Cow::Owned(format!("{:0padding$}", x)))
+    };
+    let run_len = input.chars().take_while(|&c| accepts(c)).count();
+    let mut candidates = Vec::new();
+    let mut offset = 0;
+    for c in input.chars().take(run_len) {
+        offset += c.len_utf8();
+        let candidate = &input[..offset];
+        let folded = if case_insensitive {
+            if uppercase {
+                candidate.to_ascii_uppercase()
+            } else {
+                candidate.to_ascii_lowercase()
+            }
+        } else {
+            candidate.to_string()
+        };
+        if let Some(value) = parse_alphabetic_radix(&folded, radix.clone()) {
+            if (start..=end).contains(&value) {
+                candidates.push((candidate.to_string(), offset));
             }
-            TokenIter::StrRange(range, uppercase) => range.next().map(|x| {
-                let radix = if *uppercase { 'A'..='Z' } else { 'a'..='z' };
-                to_alphabetic_radix(x, radix).into()
-            }),
         }
     }
+    candidates
 }
 
 /// Convert the usize into an alphabetic radix string
@@ -97,16 +258,15 @@ fn to_alphabetic_radix(mut x: usize, radix: RangeInclusive<char>) -> String {
     String::from_iter(digits.into_iter().rev())
 }
 
-/// Parse the alphabetic radix string into an usize
-fn parse_alphabetic_radix(s: &str, radix: RangeInclusive<char>) -> Result<usize> {
+/// Parse the alphabetic radix string into an usize, or `None` if any
+/// character falls outside `radix`.
+fn parse_alphabetic_radix(s: &str, radix: RangeInclusive<char>) -> Option<usize> {
     let (start, end) = (*radix.start(), *radix.end());
     let n = end as usize - start as usize + 1;
     s.chars().try_fold(0, |acc, x| {
-        if radix.contains(&x) {
-            Ok(acc * n + (x as usize) - (start as usize) + 1)
-        } else {
-            bail!("char '{x}' not in range '{start}-{end}'",)
-        }
+        radix
+            .contains(&x)
+            .then(|| acc * n + (x as usize) - (start as usize) + 1)
     })
 }
 
@@ -115,7 +275,7 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_token_iter() -> Result<()> {
+    fn test_token_iter() -> Result<(), PatternError> {
         let cases = vec![
             (
                 "plain",
@@ -123,9 +283,25 @@ mod tests {
                 vec!["Hello, World"],
             ),
             (
-                "set",
-                Token::new_set(["a", "b", "c", "d", "e"]),
-                vec!["a", "b", "c", "d", "e"],
+                "alt",
+                Token::new_alt(vec![
+                    Token::new_plain("a"),
+                    Token::new_plain("b"),
+                    Token::new_plain("c"),
+                ]),
+                vec!["a", "b", "c"],
+            ),
+            (
+                "nested alt and seq",
+                Token::new_alt(vec![
+                    Token::new_plain("a"),
+                    Token::new_seq(vec![
+                        Token::new_plain("b"),
+                        Token::new_alt(vec![Token::new_plain("1"), Token::new_plain("2")]),
+                    ]),
+                    Token::new_plain("c"),
+                ]),
+                vec!["a", "b1", "b2", "c"],
             ),
             (
                 "number range",
@@ -139,19 +315,24 @@ mod tests {
             ),
             (
                 "single letter range",
-                Token::new_str_range("a", "c")?,
+                Token::new_range("a", "c", 0..3, "[a-c]")?,
                 vec!["a", "b", "c"],
             ),
             (
                 "multi letters range",
-                Token::new_str_range("y", "af")?,
+                Token::new_range("y", "af", 0..4, "[y-af]")?,
                 vec!["y", "z", "aa", "ab", "ac", "ad", "ae", "af"],
             ),
             (
                 "multi uppercase letters range",
-                Token::new_str_range("WZ", "XF")?,
+                Token::new_range("WZ", "XF", 0..6, "[WZ-XF]")?,
                 vec!["WZ", "XA", "XB", "XC", "XD", "XE", "XF"],
             ),
+            (
+                "wildcard yields its own glyph",
+                Token::new_wildcard(Wildcard::Many),
+                vec!["*"],
+            ),
         ];
 
         for (name, input, expected) in cases {
@@ -162,4 +343,70 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_match_num_range() {
+        assert_eq!(
+            match_num_range("12abc", 1, 120, 0),
+            vec![("1".to_string(), 1), ("12".to_string(), 2)]
+        );
+        assert_eq!(match_num_range("099x", 80, 120, 3), vec![("099".to_string(), 3)]);
+    }
+
+    #[test]
+    fn test_match_str_range() {
+        assert_eq!(
+            match_str_range("bbzz", 51, 55, false, false),
+            vec![("bb".to_string(), 2)]
+        );
+    }
+
+    #[test]
+    fn test_match_str_range_case_insensitive() {
+        assert_eq!(
+            match_str_range("BBzz", 51, 55, false, true),
+            vec![("BB".to_string(), 2)]
+        );
+        assert_eq!(match_str_range("BBzz", 51, 55, false, false), vec![]);
+    }
+
+    #[test]
+    fn test_contains_wildcard() {
+        assert!(!Token::new_plain("plain").contains_wildcard());
+        assert!(Token::new_wildcard(Wildcard::Tree).contains_wildcard());
+        assert!(Token::new_seq(vec![
+            Token::new_plain("a"),
+            Token::new_wildcard(Wildcard::One),
+        ])
+        .contains_wildcard());
+        assert!(!Token::new_alt(vec![Token::new_plain("a"), Token::new_plain("b")])
+            .contains_wildcard());
+    }
+
+    #[test]
+    fn test_new_range_errors() {
+        assert_eq!(
+            Token::new_range("a", "Z", 0..3, "[a-Z]"),
+            Err(PatternError::MixedCaseRange {
+                fragment: "a-Z".to_string(),
+                source_code: "[a-Z]".to_string(),
+                span: 0..3,
+            })
+        );
+        assert_eq!(
+            Token::new_range("", "c", 0..2, "[-c]"),
+            Err(PatternError::EmptyRangeBound {
+                source_code: "[-c]".to_string(),
+                span: 0..2,
+            })
+        );
+        assert_eq!(
+            Token::new_range("a", "9", 0..3, "[a-9]"),
+            Err(PatternError::InvalidRangeChars {
+                fragment: "a-9".to_string(),
+                source_code: "[a-9]".to_string(),
+                span: 0..3,
+            })
+        );
+    }
 }
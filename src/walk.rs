@@ -0,0 +1,404 @@
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::pattern::{self, Options};
+use super::token::{Token, Wildcard};
+
+/// Lazily walk `root` on disk, yielding every existing path matching
+/// `root_token`. See [`Pattern::walk`](crate::pattern::Pattern::walk) for
+/// the matching rules. Apart from resolving `root` against the first
+/// component, which happens when `walk` is called (and may open `root`),
+/// work only happens as the returned iterator is advanced — a caller that stops early (e.g. `.take(1)`) never reads
+/// directories beyond what was needed to produce the items it consumed.
+pub(crate) fn walk<'a>(
+    root_token: &Token<'a>,
+    root: &Path,
+    options: Options,
+) -> impl Iterator<Item = PathBuf> + 'a {
+    let mut iter = WalkIter {
+        components: split_components(root_token),
+        options,
+        stack: Vec::new(),
+        tree_visited: HashSet::new(),
+    };
+    let first = iter.push_or_yield(root.to_path_buf(), 0);
+    first.into_iter().chain(iter)
+}
+
+/// Split a pattern's top-level tokens into one `Vec<Token>` per path
+/// component. A `/` inside plain text starts a new component; a `**`
+/// always gets a component of its own (it owns its trailing separator at
+/// parse time, and a `/` before it just ends the preceding plain text).
+/// Anything else
+/// — groups, ranges, `?`, a lone `*` — attaches to whichever component it
+/// falls in and is never itself split.
+fn split_components<'a>(root: &Token<'a>) -> Vec<Vec<Token<'a>>> {
+    let top = match root {
+        Token::Seq(children) => children.clone(),
+        other => vec![other.clone()],
+    };
+
+    let mut components: Vec<Vec<Token<'a>>> = vec![Vec::new()];
+    for token in top {
+        match token {
+            Token::Plain(s) => {
+                let mut parts = s.split('/');
+                if let Some(first) = parts.next() {
+                    push_plain(components.last_mut().unwrap(), first);
+                }
+                for part in parts {
+                    components.push(Vec::new());
+                    push_plain(components.last_mut().unwrap(), part);
+                }
+            }
+            Token::Wildcard(Wildcard::Tree) => {
+                if !components.last().unwrap().is_empty() {
+                    components.push(Vec::new());
+                }
+                components.push(vec![Token::Wildcard(Wildcard::Tree)]);
+                components.push(Vec::new());
+            }
+            other => components.last_mut().unwrap().push(other),
+        }
+    }
+
+    components.retain(|c| !c.is_empty());
+    if components.is_empty() {
+        components.push(Vec::new());
+    }
+    components
+}
+
+fn push_plain<'a>(component: &mut Vec<Token<'a>>, s: &'a str) {
+    if !s.is_empty() {
+        component.push(Token::new_plain(s));
+    }
+}
+
+/// Pending work for the walk's explicit, stack-driven depth-first search.
+/// Each variant holds the source of further candidates (a set of
+/// deterministic values, or a real `ReadDir` still being scanned) together
+/// with enough context to resume matching once a candidate is found. This
+/// replaces recursion with an explicit stack so `WalkIter::next` can return
+/// as soon as one path is found, instead of the whole subtree having to be
+/// explored up front.
+enum Task {
+    /// Try each deterministic value in turn, descending to the component
+    /// at `next_index` for each one that exists.
+    Deterministic {
+        dir: PathBuf,
+        next_index: usize,
+        values: std::vec::IntoIter<String>,
+    },
+    /// Scan real directory entries against the wildcard-containing
+    /// component at `component_index`, descending to `next_index` for each
+    /// match.
+    Wildcard {
+        entries: fs::ReadDir,
+        component_index: usize,
+        next_index: usize,
+    },
+    /// Scan real directory entries to let a bare `**` swallow one more
+    /// directory, retrying the same (unconsumed) component at
+    /// `component_index` on each one.
+    TreeDescend {
+        entries: fs::ReadDir,
+        component_index: usize,
+    },
+}
+
+struct WalkIter<'a> {
+    components: Vec<Vec<Token<'a>>>,
+    options: Options,
+    stack: Vec<Task>,
+    /// Canonicalized directories already scanned for a bare `**` descent,
+    /// so a symlink cycle (e.g. `a/b/loop -> a`) can't make `**` recurse
+    /// into the same real directory forever. This does mean two distinct
+    /// paths that happen to resolve to the same canonical directory
+    /// (hardlinks, or sibling symlinks to one target) are only descended
+    /// into once.
+    tree_visited: HashSet<PathBuf>,
+}
+
+impl<'a> WalkIter<'a> {
+    /// Resolve `dir` against the component at `index`: yield it immediately
+    /// if there are no more components and it exists, or push whatever
+    /// `Task` is needed to enumerate the next step and return `None`.
+    fn push_or_yield(&mut self, dir: PathBuf, index: usize) -> Option<PathBuf> {
+        let Some(component) = self.components.get(index) else {
+            return dir.exists().then_some(dir);
+        };
+        let next_index = index + 1;
+
+        if is_bare_tree(component) {
+            // Zero directories: the rest of the pattern may match right
+            // here...
+            let hit = self.push_or_yield(dir.clone(), next_index);
+            // ...or `**` can also swallow one more directory and keep
+            // trying, as long as we haven't already scanned this real
+            // directory for a `**` descent before (a symlink loop would
+            // otherwise make this recurse forever).
+            if self.tree_visited.insert(canonical_or_self(&dir)) {
+                if let Ok(entries) = fs::read_dir(&dir) {
+                    self.stack.push(Task::TreeDescend {
+                        entries,
+                        component_index: index,
+                    });
+                }
+            }
+            return hit;
+        }
+
+        if component.iter().any(Token::contains_wildcard) {
+            if let Ok(entries) = fs::read_dir(&dir) {
+                self.stack.push(Task::Wildcard {
+                    entries,
+                    component_index: index,
+                    next_index,
+                });
+            }
+            return None;
+        }
+
+        self.stack.push(Task::Deterministic {
+            dir,
+            next_index,
+            values: enumerate_deterministic(component).into_iter(),
+        });
+        None
+    }
+}
+
+impl<'a> Iterator for WalkIter<'a> {
+    type Item = PathBuf;
+
+    fn next(&mut self) -> Option<PathBuf> {
+        while let Some(mut task) = self.stack.pop() {
+            let step = match &mut task {
+                Task::Deterministic { dir, next_index, values } => values
+                    .by_ref()
+                    .map(|value| dir.join(value))
+                    .find(|candidate| candidate.exists())
+                    .map(|candidate| (candidate, *next_index)),
+                Task::Wildcard {
+                    entries,
+                    component_index,
+                    next_index,
+                } => loop {
+                    match entries.next() {
+                        Some(Ok(entry)) => {
+                            let name = entry.file_name();
+                            let component = &self.components[*component_index];
+                            if component_matches(component, &name, self.options) {
+                                break Some((entry.path(), *next_index));
+                            }
+                        }
+                        Some(Err(_)) => continue,
+                        None => break None,
+                    }
+                },
+                Task::TreeDescend { entries, component_index } => loop {
+                    match entries.next() {
+                        Some(Ok(entry)) if entry.path().is_dir() => {
+                            break Some((entry.path(), *component_index));
+                        }
+                        Some(_) => continue,
+                        None => break None,
+                    }
+                },
+            };
+
+            if let Some((candidate_dir, next_index)) = step {
+                // The task may still have more candidates to offer later.
+                self.stack.push(task);
+                if let Some(hit) = self.push_or_yield(candidate_dir, next_index) {
+                    return Some(hit);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Whether `component` is a lone `**`, the only shape allowed to match a
+/// variable number of whole directories instead of a single name.
+fn is_bare_tree(component: &[Token<'_>]) -> bool {
+    matches!(component, [Token::Wildcard(Wildcard::Tree)])
+}
+
+/// Whether `component` matches `name`. A lone `*` matches any name,
+/// including ones that aren't valid UTF-8; anything else needs `name` to
+/// decode so its characters can be compared.
+fn component_matches(component: &[Token<'_>], name: &OsStr, options: Options) -> bool {
+    if matches!(component, [Token::Wildcard(Wildcard::Many)]) {
+        return true;
+    }
+    name.to_str()
+        .is_some_and(|s| pattern::matches_component(component, s, options))
+}
+
+fn enumerate_deterministic(component: &[Token<'_>]) -> Vec<String> {
+    Token::new_seq(component.to_vec()).iter().collect()
+}
+
+/// `dir`'s canonical path, or `dir` itself if it can't be resolved (e.g. a
+/// dangling symlink, or a permissions error) — in which case the caller
+/// just won't get cycle protection for that one entry instead of failing
+/// the whole walk.
+fn canonical_or_self(dir: &Path) -> PathBuf {
+    dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_components() {
+        let root = Token::new_seq(vec![
+            Token::new_plain("logs/"),
+            Token::new_wildcard(Wildcard::Tree),
+            Token::new_plain("app-"),
+            Token::new_wildcard(Wildcard::One),
+            Token::new_plain(".log"),
+        ]);
+
+        assert_eq!(
+            split_components(&root),
+            vec![
+                vec![Token::new_plain("logs")],
+                vec![Token::new_wildcard(Wildcard::Tree)],
+                vec![
+                    Token::new_plain("app-"),
+                    Token::new_wildcard(Wildcard::One),
+                    Token::new_plain(".log"),
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_components_no_separator() {
+        let root = Token::new_seq(vec![
+            Token::new_plain("file-"),
+            Token::new_wildcard(Wildcard::Many),
+            Token::new_plain(".log"),
+        ]);
+
+        assert_eq!(
+            split_components(&root),
+            vec![vec![
+                Token::new_plain("file-"),
+                Token::new_wildcard(Wildcard::Many),
+                Token::new_plain(".log"),
+            ]]
+        );
+    }
+
+    #[test]
+    fn test_is_bare_tree() {
+        assert!(is_bare_tree(&[Token::new_wildcard(Wildcard::Tree)]));
+        assert!(!is_bare_tree(&[Token::new_wildcard(Wildcard::Many)]));
+        assert!(!is_bare_tree(&[
+            Token::new_plain("a"),
+            Token::new_wildcard(Wildcard::Tree),
+        ]));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_walk_breaks_symlink_cycle() {
+        use std::os::unix::fs::symlink;
+
+        let base = std::env::temp_dir().join(format!("globiter-walk-cycle-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        fs::create_dir_all(base.join("a/b")).unwrap();
+        symlink(base.join("a"), base.join("a/b/loop")).unwrap();
+
+        let root = Token::new_seq(vec![
+            Token::new_plain("a/"),
+            Token::new_wildcard(Wildcard::Tree),
+            Token::new_plain("b"),
+        ]);
+        let hits: Vec<_> = walk(&root, &base, Options::default()).collect();
+
+        fs::remove_dir_all(&base).unwrap();
+
+        // Every real "b" directory reachable by descending through the
+        // loop exactly once should be found, but the walk must terminate
+        // instead of following `loop -> a` forever.
+        assert!(hits.len() < 10, "walk did not stop at the symlink cycle: {hits:?}");
+    }
+
+    /// End-to-end coverage for `Pattern::walk` against a real directory
+    /// tree, combining a deterministic `{...}` component (expanded
+    /// directly, then checked for existence) with a wildcard component
+    /// (matched against real directory entries via `component_matches`).
+    #[test]
+    fn test_walk_deterministic_and_wildcard() {
+        use super::pattern::Pattern;
+
+        let base = std::env::temp_dir().join(format!("globiter-walk-mixed-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        fs::create_dir_all(base.join("a")).unwrap();
+        fs::create_dir_all(base.join("b")).unwrap();
+        fs::create_dir_all(base.join("c")).unwrap();
+        fs::write(base.join("a/keep.txt"), "").unwrap();
+        fs::write(base.join("a/skip.log"), "").unwrap();
+        fs::write(base.join("b/keep.txt"), "").unwrap();
+        fs::write(base.join("c/keep.txt"), "").unwrap();
+
+        let pattern = Pattern::parse("{a,b}/*.txt").unwrap();
+        let mut hits: Vec<_> = pattern.walk(&base).collect();
+        hits.sort();
+
+        fs::remove_dir_all(&base).unwrap();
+
+        assert_eq!(
+            hits,
+            vec![base.join("a/keep.txt"), base.join("b/keep.txt")],
+            "should expand {{a,b}} deterministically, then match *.txt against real entries, \
+             while leaving out both c/ (not in the set) and skip.log (doesn't match *.txt)",
+        );
+    }
+
+    /// `Options::case_insensitive` only reaches the filesystem walk through
+    /// `component_matches`/`matches_component`, which is only consulted for
+    /// components that contain a wildcard — a deterministic component is
+    /// just expanded and checked with `Path::exists`, which knows nothing
+    /// about the option. So the pattern here needs a wildcard component
+    /// (`*.png`) to actually exercise the option end to end, not just a
+    /// `{...}` group.
+    #[test]
+    fn test_walk_case_insensitive() {
+        use super::pattern::Pattern;
+
+        let base = std::env::temp_dir().join(format!("globiter-walk-case-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        fs::create_dir_all(&base).unwrap();
+        fs::write(base.join("Photo.PNG"), "").unwrap();
+
+        let pattern = Pattern::parse("*.png").unwrap();
+        let default_hits: Vec<_> = pattern.walk(&base).collect();
+
+        let pattern = Pattern::parse_with(
+            "*.png",
+            Options {
+                case_insensitive: true,
+                ..Options::default()
+            },
+        )
+        .unwrap();
+        let insensitive_hits: Vec<_> = pattern.walk(&base).collect();
+
+        fs::remove_dir_all(&base).unwrap();
+
+        assert!(
+            default_hits.is_empty(),
+            "case-sensitive walk should not match Photo.PNG against *.png: {default_hits:?}",
+        );
+        assert_eq!(insensitive_hits, vec![base.join("Photo.PNG")]);
+    }
+}